@@ -0,0 +1,204 @@
+//! Typed deserialization of parsed call arguments.
+//!
+//! `parse_recovering` hands back a [`PartialCall`] whose [`ArgValue`]s stay
+//! close to the surface grammar (raw number text, still-escaped strings) so
+//! recovery diagnostics can point at exact spans. Tool authors don't want to
+//! hand-walk that tree, though, so this module converts it into a
+//! `serde_json::Value` and, from there, into any `DeserializeOwned` type via
+//! `into_typed::<T>()`.
+
+use serde_json::{Map, Number, Value};
+
+use crate::runtime::components::tool_use::antlr::generated::antlrfclexer::ESCAPE_LITERALS;
+use crate::runtime::components::tool_use::antlr::recovering_parser::{ArgValue, PartialCall};
+
+/// Errors that can occur while converting a [`PartialCall`]'s arguments into
+/// a typed value.
+#[derive(Debug, thiserror::Error)]
+pub enum ArgsError {
+    #[error("argument '{0}' could not be parsed from the model's output")]
+    UnparsedArg(String),
+    #[error("'{0}' is not valid escaped-string content: {1}")]
+    InvalidEscape(String, String),
+    #[error("'{0}' is not a valid number: {1}")]
+    InvalidNumber(String, String),
+    #[error("failed to deserialize call arguments: {0}")]
+    Deserialize(#[from] serde_json::Error),
+}
+
+/// Adapter over a parsed call's arguments, converting them to
+/// `serde_json::Value` (or a concrete type) on demand.
+pub struct CallArgs<'a> {
+    call: &'a PartialCall,
+}
+
+impl<'a> CallArgs<'a> {
+    pub fn new(call: &'a PartialCall) -> Self {
+        CallArgs { call }
+    }
+
+    /// Converts the call's argument object into a `serde_json::Value::Object`.
+    pub fn to_json(&self) -> Result<Value, ArgsError> {
+        let mut map = Map::with_capacity(self.call.args.len());
+        for (key, value) in &self.call.args {
+            map.insert(key.clone(), arg_to_json(key, value)?);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Deserializes the call's arguments straight into `T`.
+    pub fn into_typed<T: serde::de::DeserializeOwned>(&self) -> Result<T, ArgsError> {
+        let json = self.to_json()?;
+        Ok(serde_json::from_value(json)?)
+    }
+}
+
+fn arg_to_json(key: &str, value: &ArgValue) -> Result<Value, ArgsError> {
+    match value {
+        ArgValue::Bool(b) => Ok(Value::Bool(*b)),
+        ArgValue::Null => Ok(Value::Null),
+        ArgValue::Number(raw) => number_to_json(key, raw),
+        ArgValue::String(escaped) => unescape(key, escaped).map(Value::String),
+        ArgValue::Array(items) => items
+            .iter()
+            .map(|item| arg_to_json(key, item))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        ArgValue::Error => Err(ArgsError::UnparsedArg(key.to_string())),
+    }
+}
+
+/// Parses the grammar's `NUMBER` text (`-`? `INT` (`FRAC` | `EXP`)?, or a
+/// bare `-`? `FRAC` or `-`? `EXP`) into a JSON number, preserving the
+/// integer-vs-float distinction: a `NUMBER` with no `.` and no exponent
+/// round-trips as a JSON integer, matching what the tool author's struct
+/// almost certainly expects for a count or id field. A bare `EXP` (e.g.
+/// `e5`) has no mantissa and is reported as `InvalidNumber`.
+fn number_to_json(key: &str, raw: &str) -> Result<Value, ArgsError> {
+    let is_float = raw.contains('.') || raw.contains('e') || raw.contains('E');
+    if is_float {
+        let f: f64 = raw.parse().map_err(|e: std::num::ParseFloatError| {
+            ArgsError::InvalidNumber(key.into(), e.to_string())
+        })?;
+        Number::from_f64(f)
+            .map(Value::Number)
+            .ok_or_else(|| ArgsError::InvalidNumber(key.into(), "not a finite number".into()))
+    } else {
+        let i: i64 = raw.parse().map_err(|e: std::num::ParseIntError| {
+            ArgsError::InvalidNumber(key.into(), e.to_string())
+        })?;
+        Ok(Value::Number(Number::from(i)))
+    }
+}
+
+/// Un-escapes an `ESCAPED_STRING`'s raw lexeme into plain text.
+///
+/// Per the grammar (`ESCAPED_STRING` is `ESCAPE .*? ESCAPE`), the lexeme is
+/// delimited by the `ESCAPE` literal on *both* sides -- `<escape>` or
+/// `<ctrl46>`, and the two sides need not match -- not by double quotes, and
+/// the format defines no backslash-escape mechanism for the content between
+/// them. So this strips exactly those delimiters and returns the interior
+/// verbatim, rather than quote-stripping and unescaping a JSON-string syntax
+/// the grammar doesn't produce.
+fn unescape(key: &str, raw: &str) -> Result<String, ArgsError> {
+    let after_open = ESCAPE_LITERALS
+        .iter()
+        .find_map(|delim| raw.strip_prefix(delim))
+        .ok_or_else(|| {
+            ArgsError::InvalidEscape(
+                key.to_string(),
+                format!("missing opening ESCAPE in {raw:?}"),
+            )
+        })?;
+
+    let inner = ESCAPE_LITERALS
+        .iter()
+        .find_map(|delim| after_open.strip_suffix(delim))
+        .ok_or_else(|| {
+            ArgsError::InvalidEscape(
+                key.to_string(),
+                format!("missing closing ESCAPE in {raw:?}"),
+            )
+        })?;
+
+    Ok(inner.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::components::tool_use::antlr::recovering_parser::PartialCall;
+    use serde::Deserialize;
+
+    fn call(args: Vec<(&str, ArgValue)>) -> PartialCall {
+        PartialCall {
+            name: Some("test".to_string()),
+            args: args.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+        }
+    }
+
+    #[test]
+    fn unescapes_escape_delimited_string_content() {
+        let value = unescape("greeting", "<escape>hello, world<escape>").unwrap();
+        assert_eq!(value, "hello, world");
+    }
+
+    #[test]
+    fn unescape_tolerates_mismatched_escape_literals_on_each_side() {
+        let value = unescape("greeting", "<escape>hi<ctrl46>").unwrap();
+        assert_eq!(value, "hi");
+    }
+
+    #[test]
+    fn unescape_rejects_quote_delimited_text() {
+        // The grammar never produces a `"..."`-quoted ESCAPED_STRING lexeme;
+        // text shaped like one is missing the real ESCAPE delimiters.
+        assert!(unescape("greeting", "\"hi\"").is_err());
+    }
+
+    #[test]
+    fn integers_round_trip_as_json_integers_not_floats() {
+        let c = call(vec![("count", ArgValue::Number("42".to_string()))]);
+        let json = CallArgs::new(&c).to_json().unwrap();
+        assert_eq!(json["count"], serde_json::json!(42));
+        assert!(json["count"].is_i64());
+    }
+
+    #[test]
+    fn fractional_numbers_round_trip_as_json_floats() {
+        let c = call(vec![("ratio", ArgValue::Number("1.5".to_string()))]);
+        let json = CallArgs::new(&c).to_json().unwrap();
+        assert_eq!(json["ratio"], serde_json::json!(1.5));
+    }
+
+    #[test]
+    fn deserializes_into_a_typed_struct() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Args {
+            query: String,
+            limit: i64,
+        }
+
+        let c = call(vec![
+            (
+                "query",
+                ArgValue::String("<escape>rust<escape>".to_string()),
+            ),
+            ("limit", ArgValue::Number("3".to_string())),
+        ]);
+        let parsed: Args = CallArgs::new(&c).into_typed().unwrap();
+        assert_eq!(
+            parsed,
+            Args {
+                query: "rust".to_string(),
+                limit: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn unparsed_argument_is_reported_as_an_error() {
+        let c = call(vec![("broken", ArgValue::Error)]);
+        assert!(CallArgs::new(&c).to_json().is_err());
+    }
+}
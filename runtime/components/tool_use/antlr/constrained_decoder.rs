@@ -0,0 +1,318 @@
+//! Grammar-constrained decoding driven directly by the `AntlrFcLexer` ATN.
+//!
+//! `AntlrFcLexer` (see `antlr::generated::antlrfclexer`) only tells us after the
+//! fact whether a completed string is a valid function call. This module turns
+//! the same serialized ATN into a *guarantee*: given the tokens accepted so far,
+//! it computes the set of characters the grammar still allows, and from there a
+//! boolean mask over the LM's vocabulary that callers apply to logits before
+//! sampling, so the model can never wander off the grammar.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use antlr4rust::atn::ATN;
+use antlr4rust::atn_state::ATNStateType;
+use antlr4rust::transition::{RuleTransition, Transition, TransitionType};
+
+use crate::runtime::components::tool_use::antlr::generated::antlrfclexer;
+
+/// A live ATN state paired with the call-context stack of
+/// `RuleTransition::follow_state`s to return to once each pending rule
+/// invocation's `RuleStopState` is actually reached, innermost call last.
+/// Without tracking this, there would be no way to tell "`ESCAPE` was called
+/// from `ESCAPED_STRING`, resume there" apart from "any caller, resume
+/// anywhere" -- which would expose a callee's body (e.g. `ESCAPED_STRING`'s
+/// wildcard) before the call transition that reaches it was ever taken.
+type Config = (i32, Vec<i32>);
+
+/// The set of characters admissible from a frontier, queried one character at
+/// a time against the live states' transitions rather than materialized up
+/// front -- `ESCAPED_STRING`'s content is a WILDCARD transition spanning the
+/// full Unicode range, and there is no way to enumerate an `IntervalSet`'s
+/// members from outside the `antlr4rust` crate, only to test membership.
+#[derive(Debug, Clone)]
+pub struct AdmissibleChars {
+    atn: Arc<ATN>,
+    states: BTreeSet<Config>,
+    eof: bool,
+}
+
+impl AdmissibleChars {
+    /// Whether stopping here (no further characters) yields a complete,
+    /// valid token stream.
+    pub fn allows_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Whether `c` is an admissible next character.
+    pub fn contains(&self, c: char) -> bool {
+        self.states.iter().any(|(state_idx, _)| {
+            self.atn.states[*state_idx as usize]
+                .get_transitions()
+                .iter()
+                .any(|transition| reachable_on(transition.as_ref(), c).is_some())
+        })
+    }
+}
+
+/// The live set of ATN configurations reached by the tokens accepted so far.
+///
+/// This is the "frontier" the grammar-constrained decoder advances one
+/// character at a time: at each step we take its epsilon-closure, then (once
+/// a character is chosen) move the frontier across the transitions that
+/// accept it and epsilon-close the result again.
+#[derive(Debug, Clone)]
+pub struct AtnFrontier {
+    atn: Arc<ATN>,
+    /// Already epsilon-closed.
+    states: BTreeSet<Config>,
+}
+
+impl AtnFrontier {
+    /// Starts a frontier at the lexer's mode-0 start state.
+    pub fn start() -> Self {
+        let atn = antlrfclexer::atn();
+        let start = atn.mode_to_start_state[0];
+        let mut frontier = AtnFrontier {
+            atn,
+            states: BTreeSet::new(),
+        };
+        frontier.states = frontier.epsilon_closure(&[(start, Vec::new())]);
+        frontier
+    }
+
+    /// Resumes a frontier that was parked mid-token (e.g. a number half
+    /// emitted when the sampling step ended). Unlike `start`, this does not
+    /// reset to the lexer's initial state -- callers that straddle a decode
+    /// boundary must keep the exact frontier they left off with. Resumed
+    /// states always carry an empty call context, so a state inside a pending
+    /// rule call (e.g. partway through an `<escape>` delimiter that
+    /// `ESCAPED_STRING` matches by invoking `ESCAPE`) resumes as if that call
+    /// were a whole token, without returning to its caller.
+    pub fn resume(atn: Arc<ATN>, states: impl IntoIterator<Item = i32>) -> Self {
+        let raw: Vec<Config> = states.into_iter().map(|s| (s, Vec::new())).collect();
+        let mut frontier = AtnFrontier {
+            atn,
+            states: BTreeSet::new(),
+        };
+        frontier.states = frontier.epsilon_closure(&raw);
+        frontier
+    }
+
+    /// Epsilon-closure over `seed`, following plain epsilon edges as well as
+    /// rule-invocation transitions, each of which pushes its `follow_state`
+    /// onto the context so the closure returns to the actual call site
+    /// rather than the lexer's mode-start.
+    ///
+    /// A `RuleStopState` reached with a non-empty context is just an inner
+    /// rule call (e.g. `ESCAPE` invoked by `ESCAPED_STRING`) returning: pop
+    /// the context and continue from the popped `follow_state`. Only a
+    /// `RuleStopState` reached with an *empty* context means a whole token is
+    /// done, so a fresh token is free to begin on the very next character --
+    /// that's the only point where we re-seed the mode-start state, rather
+    /// than on every rule stop regardless of call depth (which would expose
+    /// a callee's body before its call transition was ever taken).
+    fn epsilon_closure(&self, seed: &[Config]) -> BTreeSet<Config> {
+        let mode_start = self.atn.mode_to_start_state[0];
+        let mut closure = BTreeSet::new();
+        let mut stack: Vec<Config> = seed.to_vec();
+        while let Some((state_idx, ctx)) = stack.pop() {
+            if !closure.insert((state_idx, ctx.clone())) {
+                continue;
+            }
+            let state = self.atn.states[state_idx as usize].as_ref();
+            if *state.get_state_type() == ATNStateType::RuleStopState {
+                match ctx.split_last() {
+                    Some((&ret, rest)) => stack.push((ret, rest.to_vec())),
+                    None if state_idx != mode_start => stack.push((mode_start, Vec::new())),
+                    None => {}
+                }
+                continue;
+            }
+            for transition in state.get_transitions() {
+                let transition = transition.as_ref();
+                if !transition.is_epsilon() {
+                    continue;
+                }
+                if transition.get_serialization_type() == TransitionType::TRANSITION_RULE {
+                    let rule = transition.cast::<RuleTransition>();
+                    let mut callee_ctx = ctx.clone();
+                    callee_ctx.push(rule.follow_state);
+                    stack.push((rule.target, callee_ctx));
+                } else {
+                    stack.push((transition.get_target(), ctx.clone()));
+                }
+            }
+        }
+        closure
+    }
+
+    /// True if the frontier currently rests on at least one accepting state
+    /// with nothing left on its call context, i.e. stopping here yields a
+    /// complete, valid token stream rather than an unfinished inner call.
+    pub fn accepts_eof(&self) -> bool {
+        self.states.iter().any(|(idx, ctx)| {
+            ctx.is_empty()
+                && *self.atn.states[*idx as usize].get_state_type() == ATNStateType::RuleStopState
+        })
+    }
+
+    /// The set of characters (plus, possibly, EOF) the grammar allows as the
+    /// very next input character from this frontier.
+    pub fn admissible_chars(&self) -> AdmissibleChars {
+        AdmissibleChars {
+            atn: self.atn.clone(),
+            states: self.states.clone(),
+            eof: self.accepts_eof(),
+        }
+    }
+
+    /// Advances the frontier by consuming `c`, i.e. follows every outgoing
+    /// transition whose label admits `c` and epsilon-closes the result.
+    /// Returns `None` if no transition admits `c` (the frontier is stuck).
+    pub fn advance(&self, c: char) -> Option<AtnFrontier> {
+        let mut next = Vec::new();
+        for (state_idx, ctx) in &self.states {
+            let state = self.atn.states[*state_idx as usize].as_ref();
+            for transition in state.get_transitions() {
+                if let Some(target) = reachable_on(transition.as_ref(), c) {
+                    next.push((target, ctx.clone()));
+                }
+            }
+        }
+        if next.is_empty() {
+            return None;
+        }
+        Some(AtnFrontier {
+            atn: self.atn.clone(),
+            states: self.epsilon_closure(&next),
+        })
+    }
+}
+
+/// The state `transition` leads to on input `c`, or `None` if it doesn't
+/// admit `c`. Epsilon-like transitions (including `RuleTransition`, whose
+/// `matches` is `unimplemented!()`) never admit a character directly -- they
+/// are only followed during epsilon-closure -- so they're excluded up front.
+fn reachable_on(transition: &dyn Transition, c: char) -> Option<i32> {
+    if transition.is_epsilon() {
+        return None;
+    }
+    transition.get_reachable_target(c as i32)
+}
+
+/// Maps admissible grammar characters onto a boolean mask over tokenizer
+/// vocabulary ids: a vocab piece is allowed only if the frontier can advance
+/// through *every* one of its characters in sequence, not just its first --
+/// a piece can start on an admissible character and still run the frontier
+/// off the grammar partway through (e.g. `"c$$$"` right after grammar start).
+pub struct VocabMasker<'vocab> {
+    vocab: &'vocab [String],
+}
+
+impl<'vocab> VocabMasker<'vocab> {
+    pub fn new(vocab: &'vocab [String]) -> Self {
+        VocabMasker { vocab }
+    }
+
+    /// Builds the logit mask for the current frontier: `true` at index `i`
+    /// means vocab piece `i` may be sampled next.
+    pub fn mask(&self, frontier: &AtnFrontier) -> Vec<bool> {
+        self.vocab
+            .iter()
+            .map(|piece| Self::piece_is_admissible(frontier, piece))
+            .collect()
+    }
+
+    fn piece_is_admissible(frontier: &AtnFrontier, piece: &str) -> bool {
+        if piece.is_empty() {
+            return frontier.accepts_eof();
+        }
+        let mut current = frontier.clone();
+        for c in piece.chars() {
+            match current.advance(c) {
+                Some(next) => current = next,
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advance_all(frontier: AtnFrontier, text: &str) -> AtnFrontier {
+        text.chars().fold(frontier, |f, c| {
+            f.advance(c)
+                .unwrap_or_else(|| panic!("stuck on '{c}' in {text:?}"))
+        })
+    }
+
+    #[test]
+    fn admits_space_right_after_a_completed_token() {
+        // `call foo { ... }`: once `call` is fully matched, a space must
+        // still be admissible so the next token (`foo`) can begin.
+        let after_call = advance_all(AtnFrontier::start(), "call");
+        let admissible = after_call.admissible_chars();
+        assert!(
+            admissible.contains(' '),
+            "expected ' ' to be admissible after 'call'"
+        );
+    }
+
+    #[test]
+    fn admits_open_brace_after_an_id() {
+        let after_name = advance_all(AtnFrontier::start(), "call foo");
+        let admissible = after_name.admissible_chars();
+        assert!(admissible.contains('{'));
+    }
+
+    #[test]
+    fn rejects_a_character_outside_the_grammar() {
+        let start = AtnFrontier::start();
+        assert!(start.advance('#').is_none());
+    }
+
+    #[test]
+    fn admits_any_character_inside_an_escaped_strings_wildcard_body() {
+        // Inside an ESCAPED_STRING body the live transition is a WILDCARD
+        // spanning the whole codepoint space; membership must still be
+        // queryable one character at a time without enumerating it.
+        let inside_string = advance_all(AtnFrontier::start(), "call s { q: <escape>x");
+        let admissible = inside_string.admissible_chars();
+        assert!(admissible.contains('y'));
+        assert!(admissible.contains('\u{10000}'));
+    }
+
+    #[test]
+    fn closing_an_escaped_string_resumes_after_its_call_site_not_inside_escape() {
+        // ESCAPED_STRING invokes ESCAPE via a RuleTransition for both its
+        // opening and closing delimiter. Without a call-context stack, the
+        // closure had no way to tell "ESCAPE returned to ESCAPED_STRING" from
+        // "ESCAPE returned to the lexer's mode-start", and would either get
+        // stuck here or wrongly admit another ESCAPE-only character instead
+        // of the real continuations (',' or '}').
+        let after_string = advance_all(AtnFrontier::start(), "call s { q: <escape>x<escape>");
+        let admissible = after_string.admissible_chars();
+        assert!(admissible.contains(','));
+        assert!(admissible.contains('}'));
+    }
+
+    #[test]
+    fn vocab_masker_rejects_a_piece_that_only_has_a_valid_first_character() {
+        let vocab = vec!["call".to_string(), "c$$$".to_string()];
+        let masker = VocabMasker::new(&vocab);
+        let mask = masker.mask(&AtnFrontier::start());
+        assert_eq!(mask, vec![true, false]);
+    }
+
+    #[test]
+    fn vocab_masker_allows_a_fully_admissible_multi_char_piece() {
+        let vocab = vec!["call".to_string()];
+        let masker = VocabMasker::new(&vocab);
+        let mask = masker.mask(&AtnFrontier::start());
+        assert_eq!(mask, vec![true]);
+    }
+}
@@ -44,6 +44,17 @@ pub const CALL: i32 = 12;
 pub const ID: i32 = 13;
 pub const WS: i32 = 14;
 pub const channelNames: [&'static str; 0 + 2] = ["DEFAULT_TOKEN_CHANNEL", "HIDDEN"];
+/// Index of `channelNames`'s `"HIDDEN"` channel, for hand-written code that
+/// needs to filter hidden-channel tokens without hard-coding the channel
+/// number. The grammar's `WS` rule skips whitespace rather than emitting it
+/// on this channel. `i32` to match `Token::get_channel`'s return type.
+pub(crate) const HIDDEN_CHANNEL: i32 = 1;
+/// The grammar's `ESCAPE` literals (see rule `ESCAPE` below): `ESCAPED_STRING`
+/// is `ESCAPE .*? ESCAPE`, delimited by one of these two spellings on each
+/// side, not by double quotes. Shared so hand-written code that needs to
+/// recognize or strip them doesn't have to hard-code the grammar's literal
+/// text in more than one place.
+pub(crate) const ESCAPE_LITERALS: [&str; 2] = ["<escape>", "<ctrl46>"];
 
 pub const modeNames: [&'static str; 1] = ["DEFAULT_MODE"];
 
@@ -233,6 +244,13 @@ impl<'input, Input: CharStream<From<'input>>> TokenSource<'input> for AntlrFcLex
     }
 }
 
+/// Exposes the deserialized ATN for hand-written code that sits alongside
+/// this generated lexer (e.g. constrained decoding) without requiring a
+/// live `AntlrFcLexer` instance.
+pub(crate) fn atn() -> Arc<ATN> {
+    _ATN.clone()
+}
+
 lazy_static! {
     static ref _ATN: Arc<ATN> =
         Arc::new(ATNDeserializer::new(None).deserialize(&mut _serializedATN.iter()));
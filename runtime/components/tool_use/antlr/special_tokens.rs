@@ -0,0 +1,321 @@
+//! Configurable special-token vocabulary for model-specific call wrappers.
+//!
+//! The grammar itself bakes in one fixed pair of control markers (`ESCAPE`
+//! matches `<escape>`/`<ctrl46>`). Other model families wrap the same
+//! JSON-ish call payload in their own sentinels (`<tool_call>`,
+//! `<|python_tag|>`, `[TOOL_CALLS]`, ...). Rather than forking the grammar per
+//! family, `SpecialTokenSet` lets callers register those markers at
+//! construction time; `ConfigurableFcLexer` strips them and lexes the
+//! remaining payload with the generated `AntlrFcLexer`, merging both into one
+//! token stream where the markers sit on their own channel, so a parser sees
+//! a uniform stream regardless of which wrapper the model used and can strip
+//! non-default channels without special-casing each family's sentinel.
+
+use antlr4rust::token::Token as AntlrToken;
+use antlr4rust::token_factory::CommonTokenFactory;
+use antlr4rust::TokenSource;
+
+use crate::runtime::components::tool_use::antlr::generated::antlrfclexer::{
+    AntlrFcLexer, ESCAPE_LITERALS,
+};
+
+/// Channel carrying recognized special-token markers, kept separate from
+/// `DEFAULT_TOKEN_CHANNEL` and `HIDDEN` so a parser can strip them uniformly
+/// instead of special-casing each model family's sentinel.
+pub const SPECIAL_TOKEN_CHANNEL: i32 = 2;
+
+/// Synthetic token type for a configured marker (e.g. `<tool_call>`).
+pub const SPECIAL_MARKER: i32 = 1000;
+/// Synthetic token type for an unrecognized `<...>` wrapper: lexed to a
+/// single opaque token instead of failing, per model families we don't know
+/// about yet.
+pub const OPAQUE_WRAPPER: i32 = 1001;
+
+/// The set of model-specific sentinel markers to recognize around a call
+/// payload, configured once at construction time instead of per grammar
+/// fork.
+#[derive(Debug, Clone, Default)]
+pub struct SpecialTokenSet {
+    markers: Vec<String>,
+}
+
+impl SpecialTokenSet {
+    pub fn new() -> Self {
+        SpecialTokenSet {
+            markers: Vec::new(),
+        }
+    }
+
+    /// Registers a marker (e.g. `"<tool_call>"`, `"[TOOL_CALLS]"`). Longer
+    /// markers are matched first so one marker can't shadow a longer one
+    /// that shares a prefix. An empty marker is ignored: it would match
+    /// every position while consuming nothing, spinning the stripping loops
+    /// in `split` forever.
+    pub fn with_marker(mut self, marker: impl Into<String>) -> Self {
+        let marker = marker.into();
+        if marker.is_empty() {
+            return self;
+        }
+        self.markers.push(marker);
+        self.markers.sort_by_key(|m| std::cmp::Reverse(m.len()));
+        self
+    }
+
+    fn match_at<'a>(&self, text: &'a str) -> Option<&'a str> {
+        self.markers
+            .iter()
+            .find(|m| text.starts_with(m.as_str()))
+            .map(|m| &text[..m.len()])
+    }
+
+    fn match_suffix<'a>(&self, text: &'a str) -> Option<&'a str> {
+        self.markers
+            .iter()
+            .find(|m| text.ends_with(m.as_str()))
+            .map(|m| &text[text.len() - m.len()..])
+    }
+}
+
+/// A recognized special-token span, reported separately by `split`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecialToken {
+    pub token_type: i32,
+    pub text: String,
+    /// Byte offset of this marker within the original, unstripped input.
+    pub start: usize,
+}
+
+/// A token in the unified stream `ConfigurableFcLexer::lex` produces: either
+/// a recognized special-token marker/wrapper, on `SPECIAL_TOKEN_CHANNEL`, or
+/// a token from the stripped payload, on the channel `AntlrFcLexer` assigned
+/// it (`DEFAULT_TOKEN_CHANNEL`; the grammar's `WS` rule skips whitespace, so
+/// no payload token is on `HIDDEN_CHANNEL`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub token_type: i32,
+    pub channel: i32,
+    pub text: String,
+    /// Byte offset of this token within the original, unstripped input.
+    pub start: usize,
+}
+
+/// A lexer that recognizes model-specific wrapper markers around a call
+/// payload before delegating the rest to `AntlrFcLexer`.
+pub struct ConfigurableFcLexer {
+    specials: SpecialTokenSet,
+}
+
+impl ConfigurableFcLexer {
+    pub fn new(specials: SpecialTokenSet) -> Self {
+        ConfigurableFcLexer { specials }
+    }
+
+    /// Splits `input` into the special-token markers wrapping the call
+    /// payload and the payload itself, unwrapped but otherwise untouched,
+    /// for `AntlrFcLexer` to lex normally.
+    ///
+    /// Markers are only stripped from the *outside* of the payload -- the
+    /// leading run and the trailing run -- never from the interior. The
+    /// interior is exactly what `AntlrFcLexer` needs to see as-is, including
+    /// the grammar's own `<escape>`/`<ctrl46>` delimiters around
+    /// `ESCAPED_STRING` arguments; scanning through the interior for
+    /// `<...>`-shaped text would shred those delimiters along with any
+    /// model-specific wrapper that happened to look similar. Unknown
+    /// `<...>` wrappers at the edges lex to a single `OPAQUE_WRAPPER` token
+    /// rather than failing, so an unsupported model family degrades
+    /// gracefully instead of breaking the whole parse; the grammar's own
+    /// `ESCAPE` literals are never treated as wrappers, even at the edges.
+    pub fn split(&self, input: &str) -> (Vec<SpecialToken>, String) {
+        let (leading, trailing, front, back) = self.split_bounds(input);
+        let mut specials = leading;
+        specials.extend(trailing);
+        (specials, input[front..back].to_string())
+    }
+
+    /// Strips markers from both edges of `input`, returning the leading and
+    /// trailing markers (each already in left-to-right order) plus the byte
+    /// bounds of the payload in between.
+    fn split_bounds(&self, input: &str) -> (Vec<SpecialToken>, Vec<SpecialToken>, usize, usize) {
+        let mut leading = Vec::new();
+        let mut front = 0usize;
+        let mut back = input.len();
+
+        loop {
+            let rest = &input[front..back];
+            if let Some(marker) = self.specials.match_at(rest) {
+                leading.push(SpecialToken {
+                    token_type: SPECIAL_MARKER,
+                    text: marker.to_string(),
+                    start: front,
+                });
+                front += marker.len();
+                continue;
+            }
+            if rest.starts_with('<') && !ESCAPE_LITERALS.iter().any(|e| rest.starts_with(e)) {
+                if let Some(end) = rest.find('>') {
+                    let wrapper = rest[..=end].to_string();
+                    let start = front;
+                    front += wrapper.len();
+                    leading.push(SpecialToken {
+                        token_type: OPAQUE_WRAPPER,
+                        text: wrapper,
+                        start,
+                    });
+                    continue;
+                }
+            }
+            break;
+        }
+
+        let mut trailing = Vec::new();
+        loop {
+            let rest = &input[front..back];
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(marker) = self.specials.match_suffix(rest) {
+                let start = back - marker.len();
+                trailing.push(SpecialToken {
+                    token_type: SPECIAL_MARKER,
+                    text: marker.to_string(),
+                    start,
+                });
+                back = start;
+                continue;
+            }
+            if rest.ends_with('>') && !ESCAPE_LITERALS.iter().any(|e| rest.ends_with(e)) {
+                if let Some(open) = rest.rfind('<') {
+                    let wrapper = rest[open..].to_string();
+                    let start = front + open;
+                    back = start;
+                    trailing.push(SpecialToken {
+                        token_type: OPAQUE_WRAPPER,
+                        text: wrapper,
+                        start,
+                    });
+                    continue;
+                }
+            }
+            break;
+        }
+        trailing.reverse();
+
+        (leading, trailing, front, back)
+    }
+
+    /// Splits `input`, lexes the stripped payload with `AntlrFcLexer`, and
+    /// returns one token stream in source order: leading markers, then the
+    /// payload's tokens (offset back into `input`'s coordinates), then
+    /// trailing markers.
+    pub fn lex(&self, input: &str) -> Vec<Token> {
+        let (leading, trailing, front, back) = self.split_bounds(input);
+        let payload = &input[front..back];
+
+        let stream = antlr4rust::InputStream::new(payload);
+        let tf = CommonTokenFactory;
+        let mut lexer = AntlrFcLexer::new_with_token_factory(stream, &tf);
+
+        let mut tokens: Vec<Token> = leading.into_iter().map(special_to_token).collect();
+        loop {
+            let tok = lexer.next_token();
+            if tok.get_token_type() == antlr4rust::token::TOKEN_EOF {
+                break;
+            }
+            tokens.push(Token {
+                token_type: tok.get_token_type(),
+                channel: tok.get_channel(),
+                text: tok.get_text().to_string(),
+                start: front + tok.get_start() as usize,
+            });
+        }
+        tokens.extend(trailing.into_iter().map(special_to_token));
+        tokens
+    }
+}
+
+fn special_to_token(special: SpecialToken) -> Token {
+    Token {
+        token_type: special.token_type,
+        channel: SPECIAL_TOKEN_CHANNEL,
+        text: special.text,
+        start: special.start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::components::tool_use::antlr::generated::antlrfclexer::HIDDEN_CHANNEL;
+
+    #[test]
+    fn strips_a_configured_marker_around_the_payload() {
+        let specials = SpecialTokenSet::new().with_marker("<tool_call>");
+        let lexer = ConfigurableFcLexer { specials };
+        let (markers, payload) = lexer.split("<tool_call>call ping { }<tool_call>");
+        assert_eq!(payload, "call ping { }");
+        assert_eq!(markers.len(), 2);
+        assert!(markers.iter().all(|m| m.token_type == SPECIAL_MARKER));
+        assert_eq!(markers[0].start, 0);
+    }
+
+    #[test]
+    fn does_not_touch_the_grammars_own_escape_delimiters_inside_the_payload() {
+        // The `<escape>`/`<ctrl46>` pair here belongs to an ESCAPED_STRING
+        // argument, not to a model wrapper -- it must survive untouched even
+        // though it is `<...>`-shaped, and even when a real wrapper marker
+        // surrounds the whole thing.
+        let specials = SpecialTokenSet::new().with_marker("<tool_call>");
+        let lexer = ConfigurableFcLexer { specials };
+        let input = "<tool_call>call say { text: <escape>hi<escape> }<tool_call>";
+        let (markers, payload) = lexer.split(input);
+        assert_eq!(payload, "call say { text: <escape>hi<escape> }");
+        assert_eq!(markers.len(), 2);
+    }
+
+    #[test]
+    fn unknown_angle_wrapper_becomes_a_single_opaque_token() {
+        let specials = SpecialTokenSet::new();
+        let lexer = ConfigurableFcLexer { specials };
+        let (markers, payload) = lexer.split("<|python_tag|>call ping { }<|python_tag|>");
+        assert_eq!(payload, "call ping { }");
+        assert_eq!(markers.len(), 2);
+        assert!(markers.iter().all(|m| m.token_type == OPAQUE_WRAPPER));
+    }
+
+    #[test]
+    fn an_empty_marker_is_ignored_instead_of_matching_everywhere() {
+        let specials = SpecialTokenSet::new().with_marker("");
+        let lexer = ConfigurableFcLexer { specials };
+        let (markers, payload) = lexer.split("call ping { }");
+        assert_eq!(markers.len(), 0);
+        assert_eq!(payload, "call ping { }");
+    }
+
+    #[test]
+    fn lex_merges_markers_and_payload_tokens_with_markers_on_their_own_channel() {
+        let specials = SpecialTokenSet::new().with_marker("<tool_call>");
+        let lexer = ConfigurableFcLexer { specials };
+        let tokens = lexer.lex("<tool_call>call ping { }<tool_call>");
+        let channels: Vec<i32> = tokens.iter().map(|t| t.channel).collect();
+        assert_eq!(
+            channels,
+            vec![
+                SPECIAL_TOKEN_CHANNEL,
+                0,
+                0,
+                0,
+                0,
+                SPECIAL_TOKEN_CHANNEL,
+            ]
+        );
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["<tool_call>", "call", "ping", "{", "}", "<tool_call>"]
+        );
+        assert!(tokens
+            .iter()
+            .filter(|t| t.channel != SPECIAL_TOKEN_CHANNEL)
+            .all(|t| t.channel != HIDDEN_CHANNEL));
+    }
+}
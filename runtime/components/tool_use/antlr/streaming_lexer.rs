@@ -0,0 +1,379 @@
+//! Incremental front-end for `AntlrFcLexer`.
+//!
+//! `AntlrFcLexer::new` wants a complete `CharStream`, but function-calling
+//! output arrives incrementally as the model decodes. `StreamingFcLexer` lets
+//! callers `feed` appended chunks and get back fully-recognized tokens as soon
+//! as they complete, while keeping a pending buffer for lexemes that straddle
+//! a chunk boundary (an `ESCAPED_STRING` still open, a `NUMBER` that might
+//! grow another digit, a `call`/`<escape>` keyword seen only halfway).
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use antlr4rust::atn::ATN;
+use antlr4rust::atn_state::ATNStateType;
+use antlr4rust::input_stream::InputStream;
+use antlr4rust::token::{Token as AntlrToken, TOKEN_EOF};
+use antlr4rust::token_factory::CommonTokenFactory;
+use antlr4rust::transition::{RuleTransition, TransitionType};
+use antlr4rust::TokenSource;
+
+use crate::runtime::components::tool_use::antlr::generated::antlrfclexer::{
+    self, AntlrFcLexer, HIDDEN_CHANNEL,
+};
+
+/// A token recognized by `StreamingFcLexer`, detached from the borrowed
+/// `CharStream` the generated lexer normally hands back.
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub token_type: i32,
+    pub text: String,
+    /// Offset of the first character of this token within the full stream
+    /// fed so far (i.e. across all `feed` calls, not just the current chunk).
+    pub start: isize,
+    pub stop: isize,
+}
+
+/// Whether a finished stream ended cleanly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishOutcome {
+    /// The stream ended on an ATN accepting boundary with nothing pending.
+    Clean,
+    /// Input remained in the pending buffer that never reached an accepting
+    /// state (e.g. an unterminated `ESCAPED_STRING`).
+    Truncated,
+}
+
+/// A live ATN state paired with the call-context stack of
+/// `RuleTransition::follow_state`s to return to once each pending rule
+/// invocation's `RuleStopState` is actually reached, innermost call last. See
+/// `constrained_decoder::Config` for why this is necessary: without it, a
+/// call into e.g. `ESCAPE` has no way to tell "resume at the caller's
+/// follow_state" apart from "resume anywhere", which exposes the callee's
+/// body before its call transition was ever taken.
+type Config = (i32, Vec<i32>);
+
+/// Tracks progress through exactly one lexeme, via a bare ATN epsilon-closure
+/// rather than `constrained_decoder`'s `AtnFrontier`.
+///
+/// `AtnFrontier` deliberately re-admits the lexer's mode-start alternatives
+/// as soon as a live state is a rule stop with nothing left on its call
+/// context, because for grammar-constrained decoding "a new token may
+/// legally start here too" is exactly the question being answered. That
+/// reseeding is exactly what would make this type useless as a
+/// lexeme-boundary signal: a space right after any completed token (e.g.
+/// after `"call"`) would always be admissible, so `advance` would never get
+/// stuck and `feed` would never split well-formed input into separate
+/// tokens. `LexemeFrontier` never reseeds -- it only tracks how far the
+/// *current* lexeme can still extend, so getting stuck genuinely means
+/// "nothing continues this lexeme", the signal `StreamingFcLexer` needs to
+/// flush.
+struct LexemeFrontier {
+    atn: Arc<ATN>,
+    states: BTreeSet<Config>,
+}
+
+impl LexemeFrontier {
+    fn start() -> Self {
+        let atn = antlrfclexer::atn();
+        let seed = atn.mode_to_start_state[0];
+        let states = Self::epsilon_closure(&atn, &[(seed, Vec::new())]);
+        LexemeFrontier { atn, states }
+    }
+
+    /// Epsilon-closure over `seed`, following plain epsilon edges and
+    /// rule-invocation transitions. A `RuleStopState` reached with a
+    /// non-empty context is an inner rule call (e.g. `ESCAPE` invoked by
+    /// `ESCAPED_STRING`) returning: pop the context and resume from the
+    /// popped `follow_state`. A `RuleStopState` reached with an empty
+    /// context means the lexeme is complete -- `accepts` reports that, but
+    /// this closure does not reseed the mode-start state the way
+    /// `constrained_decoder::AtnFrontier` does, since a lexeme frontier's
+    /// whole purpose is to get stuck exactly there.
+    fn epsilon_closure(atn: &ATN, seed: &[Config]) -> BTreeSet<Config> {
+        let mut closure = BTreeSet::new();
+        let mut stack: Vec<Config> = seed.to_vec();
+        while let Some((state_idx, ctx)) = stack.pop() {
+            if !closure.insert((state_idx, ctx.clone())) {
+                continue;
+            }
+            let state = atn.states[state_idx as usize].as_ref();
+            if *state.get_state_type() == ATNStateType::RuleStopState {
+                if let Some((&ret, rest)) = ctx.split_last() {
+                    stack.push((ret, rest.to_vec()));
+                }
+                continue;
+            }
+            for transition in state.get_transitions() {
+                let transition = transition.as_ref();
+                if !transition.is_epsilon() {
+                    continue;
+                }
+                if transition.get_serialization_type() == TransitionType::TRANSITION_RULE {
+                    let rule = transition.cast::<RuleTransition>();
+                    let mut callee_ctx = ctx.clone();
+                    callee_ctx.push(rule.follow_state);
+                    stack.push((rule.target, callee_ctx));
+                } else {
+                    stack.push((transition.get_target(), ctx.clone()));
+                }
+            }
+        }
+        closure
+    }
+
+    /// True if the lexeme matched so far is already a complete, valid token.
+    fn accepts(&self) -> bool {
+        self.states.iter().any(|(idx, ctx)| {
+            ctx.is_empty()
+                && *self.atn.states[*idx as usize].get_state_type() == ATNStateType::RuleStopState
+        })
+    }
+
+    /// Consumes `c`, returning `None` if nothing can extend the current
+    /// lexeme with it.
+    fn advance(&self, c: char) -> Option<Self> {
+        let mut next = Vec::new();
+        for (state_idx, ctx) in &self.states {
+            let state = self.atn.states[*state_idx as usize].as_ref();
+            for transition in state.get_transitions() {
+                let transition = transition.as_ref();
+                if transition.is_epsilon() {
+                    continue;
+                }
+                if let Some(target) = transition.get_reachable_target(c as i32) {
+                    next.push((target, ctx.clone()));
+                }
+            }
+        }
+        if next.is_empty() {
+            return None;
+        }
+        Some(LexemeFrontier {
+            atn: self.atn.clone(),
+            states: Self::epsilon_closure(&self.atn, &next),
+        })
+    }
+}
+
+/// Feeds `AntlrFcLexer` with appended chunks and yields complete tokens as
+/// they are recognized.
+///
+/// Resuming the generated `LexerATNSimulator` mid-lexeme isn't exposed by
+/// `AntlrFcLexer`'s public surface, so throughput is kept linear by tracking
+/// lexeme boundaries with the cheaper [`LexemeFrontier`] instead: each fed
+/// character advances the frontier by one step (amortized O(1)), and the
+/// full `AntlrFcLexer` is only re-invoked once per *completed* lexeme, over
+/// just that lexeme's text, to materialize its real token type and text.
+/// Every character ends up in exactly one such materialization pass, so
+/// total work is O(total bytes fed), not O(bytes²) -- including for a single
+/// `ESCAPED_STRING` or `NUMBER` streamed in many small chunks.
+pub struct StreamingFcLexer {
+    /// Lexeme-boundary frontier for the token currently being accumulated in
+    /// `buffer`.
+    frontier: LexemeFrontier,
+    /// Characters accumulated since the last completed lexeme.
+    buffer: String,
+    /// Absolute offset of `buffer`'s first character within the full stream.
+    consumed: isize,
+}
+
+impl StreamingFcLexer {
+    pub fn new() -> Self {
+        StreamingFcLexer {
+            frontier: LexemeFrontier::start(),
+            buffer: String::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Appends `chunk` and returns every token that can now be recognized
+    /// with certainty, i.e. every token except the lexeme at the very end of
+    /// the buffer, which is held back until a following character or
+    /// `finish` ends it, even when it cannot grow further (e.g. a `}`).
+    pub fn feed(&mut self, chunk: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        for c in chunk.chars() {
+            if let Some(next) = self.frontier.advance(c) {
+                self.buffer.push(c);
+                self.frontier = next;
+                continue;
+            }
+
+            // Nothing extends the lexeme in `buffer` with `c`. If `buffer`
+            // is already a complete token, that's the real boundary: flush
+            // it and start fresh on `c`. If it isn't (e.g. `c` is outright
+            // outside the grammar), there's no valid lexeme to cut at, so
+            // fold `c` into the same buffer and let the eventual relex
+            // report the malformed input rather than silently dropping it.
+            if self.frontier.accepts() {
+                tokens.extend(self.flush_buffer());
+                self.frontier = LexemeFrontier::start();
+                if let Some(next) = self.frontier.advance(c) {
+                    self.buffer.push(c);
+                    self.frontier = next;
+                    continue;
+                }
+            }
+            self.buffer.push(c);
+        }
+        tokens
+    }
+
+    /// Flushes whatever remains in the buffer, treating it as the true end
+    /// of input. Returns the final tokens plus whether the stream ended at
+    /// an accepting boundary.
+    pub fn finish(mut self) -> (Vec<Token>, FinishOutcome) {
+        let outcome = if self.buffer.is_empty() || self.frontier.accepts() {
+            FinishOutcome::Clean
+        } else {
+            FinishOutcome::Truncated
+        };
+        (self.flush_buffer(), outcome)
+    }
+
+    /// Lexes `self.buffer` (a single maximal lexeme) with `AntlrFcLexer` to
+    /// get its real token type and text, drops any hidden-channel tokens
+    /// (whitespace yields none: the grammar's `WS` rule skips it), and
+    /// advances `self.consumed` past it.
+    fn flush_buffer(&mut self) -> Vec<Token> {
+        if self.buffer.is_empty() {
+            return Vec::new();
+        }
+
+        // `lexer` borrows `self.buffer` for its whole lifetime, so it has to
+        // be dropped (end of this block) before `self.buffer` can be mutated
+        // below -- otherwise the borrow checker sees a live immutable borrow
+        // through `lexer` clash with `self.buffer.clear()`.
+        let tokens = {
+            let input = InputStream::new(self.buffer.as_str());
+            let tf = CommonTokenFactory;
+            let mut lexer = AntlrFcLexer::new_with_token_factory(input, &tf);
+
+            let mut tokens = Vec::new();
+            loop {
+                let tok = lexer.next_token();
+                if tok.get_token_type() == TOKEN_EOF {
+                    break;
+                }
+                if tok.get_channel() != HIDDEN_CHANNEL {
+                    tokens.push(Token {
+                        token_type: tok.get_token_type(),
+                        text: tok.get_text().to_string(),
+                        start: self.consumed + tok.get_start(),
+                        stop: self.consumed + tok.get_stop(),
+                    });
+                }
+            }
+            tokens
+        };
+
+        self.consumed += self.buffer.len() as isize;
+        self.buffer.clear();
+        tokens
+    }
+}
+
+impl Default for StreamingFcLexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_feed_yields_tokens_in_one_pass() {
+        let mut lexer = StreamingFcLexer::new();
+        let tokens = lexer.feed("call ping { }");
+        let (tail, outcome) = lexer.finish();
+        let all: Vec<_> = tokens.into_iter().chain(tail).collect();
+        assert_eq!(outcome, FinishOutcome::Clean);
+        let texts: Vec<&str> = all.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["call", "ping", "{", "}"]);
+    }
+
+    #[test]
+    fn byte_by_byte_feed_reassembles_the_same_tokens() {
+        let mut lexer = StreamingFcLexer::new();
+        let mut tokens = Vec::new();
+        for c in "call ping { id: 1 }".chars() {
+            tokens.extend(lexer.feed(&c.to_string()));
+        }
+        let (tail, outcome) = lexer.finish();
+        tokens.extend(tail);
+        assert_eq!(outcome, FinishOutcome::Clean);
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["call", "ping", "{", "id", ":", "1", "}"]);
+    }
+
+    #[test]
+    fn a_lexeme_split_across_chunks_is_not_emitted_until_it_completes() {
+        // With the mode-start-reseeding `AtnFrontier` used as the boundary
+        // signal, `feed("call pi")` never got stuck (a space was always
+        // "admissible" right through the middle of "pi"), so it wrongly
+        // returned an empty `Vec` instead of `vec!["call"]`.
+        let mut lexer = StreamingFcLexer::new();
+        let first = lexer.feed("call pi");
+        assert_eq!(
+            first.iter().map(|t| t.text.as_str()).collect::<Vec<_>>(),
+            vec!["call"]
+        );
+        // The trailing `"}"` is the last lexeme fed. `feed` holds back the
+        // last lexeme even when it cannot grow, so it's only surfaced once
+        // `finish` confirms nothing follows it.
+        let mut second = lexer.feed("ng { }");
+        let (tail, outcome) = lexer.finish();
+        second.extend(tail);
+        assert_eq!(outcome, FinishOutcome::Clean);
+        let texts: Vec<&str> = second.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["ping", "{", "}"]);
+    }
+
+    #[test]
+    fn an_escaped_string_is_one_token_even_with_escape_split_across_chunks() {
+        // ESCAPED_STRING calls the ESCAPE rule for both its opening
+        // and closing delimiter. Without a call-context stack tracking which
+        // call is pending, the lexeme frontier would lose track of whether
+        // it had returned from ESCAPE into ESCAPED_STRING or back to the
+        // lexer's mode-start, and could split or mis-tokenize the string.
+        let mut lexer = StreamingFcLexer::new();
+        let mut tokens = lexer.feed("call s { q: <esc");
+        tokens.extend(lexer.feed("ape>hi<escape>"));
+        let (tail, outcome) = lexer.finish();
+        tokens.extend(tail);
+        assert_eq!(outcome, FinishOutcome::Clean);
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["call", "s", "{", "q", ":", "<escape>hi<escape>"]
+        );
+    }
+
+    #[test]
+    fn unterminated_input_reports_truncated() {
+        // The lexeme left pending at the end has to be genuinely unterminated
+        // at the *lexeme* level -- a missing closing `}` is a parser-level
+        // concern (`recovering_parser.rs`'s domain): in `call ping { id: 1`
+        // the pending `"1"` is already a syntactically complete `NUMBER`, so
+        // that input reports `Clean`. An
+        // `ESCAPED_STRING` missing its closing `ESCAPE` delimiter is the
+        // canonical example of a lexeme that never reaches an accepting
+        // state.
+        let mut lexer = StreamingFcLexer::new();
+        lexer.feed("call ping { q: <escape>hi");
+        let (_, outcome) = lexer.finish();
+        assert_eq!(outcome, FinishOutcome::Truncated);
+    }
+
+    #[test]
+    fn whitespace_tokens_are_not_surfaced_to_the_caller() {
+        let mut lexer = StreamingFcLexer::new();
+        let mut tokens = lexer.feed("call ping {  }");
+        let (tail, _) = lexer.finish();
+        tokens.extend(tail);
+        assert!(tokens.iter().all(|t| t.text.trim() == t.text));
+    }
+}